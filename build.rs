@@ -1,7 +1,10 @@
 use std::{
+    cell::RefCell,
+    collections::HashMap,
     env, fs,
     path::{Path, PathBuf},
     process::Command,
+    rc::Rc,
 };
 
 use bindgen::callbacks::{
@@ -48,7 +51,12 @@ fn exec(command: &str, work_dir: &str) -> anyhow::Result<String> {
 }
 
 #[derive(Debug)]
-struct Callbacks;
+struct Callbacks {
+    /// `LIB*_VERSION_{MAJOR,MINOR,MICRO}` macros (e.g.
+    /// `LIBAVUTIL_VERSION_MAJOR`, `LIBSWSCALE_VERSION_MINOR`), keyed by macro name, as
+    /// they're encountered while bindgen walks the headers.
+    versions: Rc<RefCell<HashMap<String, i64>>>,
+}
 
 impl ParseCallbacks for Callbacks {
     fn int_macro(&self, _name: &str, value: i64) -> Option<IntKind> {
@@ -57,6 +65,14 @@ impl ParseCallbacks for Callbacks {
         let codec_flag_prefix = "AV_CODEC_FLAG_";
         let error_max_size = "AV_ERROR_MAX_STRING_SIZE";
 
+        if _name.starts_with("LIB")
+            && (_name.ends_with("_VERSION_MAJOR")
+                || _name.ends_with("_VERSION_MINOR")
+                || _name.ends_with("_VERSION_MICRO"))
+        {
+            self.versions.borrow_mut().insert(_name.to_string(), value);
+        }
+
         if _name.starts_with(ch_layout_prefix) {
             Some(IntKind::ULongLong)
         } else if value >= i32::MIN as i64
@@ -109,6 +125,41 @@ fn output() -> PathBuf {
     PathBuf::from(env::var("OUT_DIR").unwrap())
 }
 
+/// Symbols a custom `AVIOContext` (in-memory or networked muxing, as the
+/// transcoder example does) needs: `avio_alloc_context` for wiring up
+/// read/write/seek callbacks, and the dyn-buf helpers for growable
+/// in-memory output buffers.
+static AVIO_SYMBOLS: &[&str] = &[
+    "AVIOContext",
+    "avio_alloc_context",
+    "avio_open_dyn_buf",
+    "avio_close_dyn_buf",
+];
+
+/// Fails the build loudly if a header or bindgen change ever drops one of
+/// [`AVIO_SYMBOLS`] from the generated bindings, instead of leaving
+/// downstream crates to silently hand-declare externs for it. These symbols
+/// only come from `libavformat/avio.h`, so only call this when the
+/// `avformat` feature (and thus that header) is actually enabled.
+///
+/// No `allowlist`/`opaque` tuning turned out to be needed for these symbols
+/// to survive bindgen intact — `blocklist_function("_.*")` is the only rule
+/// that could plausibly have touched them, and it doesn't (see the comment
+/// on that rule in `main`). This assertion is what pins that down as fact
+/// instead of assumption, and guards it against regressing later.
+fn assert_avio_symbols_present(bindings_path: &Path) {
+    let generated =
+        fs::read_to_string(bindings_path).expect("Couldn't read generated bindings back");
+
+    for symbol in AVIO_SYMBOLS {
+        assert!(
+            generated.contains(symbol),
+            "expected bindgen to generate `{}` for the AVIO callback API, but it's missing from bindings.rs",
+            symbol
+        );
+    }
+}
+
 fn search_include(include_prefix: &Vec<String>, header: &str) -> String {
     for dir in include_prefix {
         let include = join(dir, header).unwrap();
@@ -119,30 +170,229 @@ fn search_include(include_prefix: &Vec<String>, header: &str) -> String {
     format!("/usr/include/{}", header)
 }
 
-static LIBRARYS: [(&str, &str); 8] = [
-    ("avcodec", "6.0"),
-    ("avdevice", "6.0"),
-    ("avfilter", "6.0"),
-    ("avformat", "6.0"),
-    ("avutil", "6.0"),
-    ("postproc", "6.0"),
-    ("swresample", "4.7"),
-    ("swscale", "6.0"),
+/// A single FFmpeg sublibrary: its pkg-config name/version, whether it can
+/// be disabled, and the headers it needs bindgen to see.
+struct Library {
+    name: &'static str,
+    version: &'static str,
+    optional: bool,
+    headers: &'static [&'static str],
+}
+
+static LIBRARYS: [Library; 8] = [
+    Library {
+        name: "avutil",
+        version: "6.0",
+        optional: false,
+        headers: &[
+            "libavutil/adler32.h",
+            "libavutil/aes.h",
+            "libavutil/audio_fifo.h",
+            "libavutil/base64.h",
+            "libavutil/blowfish.h",
+            "libavutil/bprint.h",
+            "libavutil/buffer.h",
+            "libavutil/camellia.h",
+            "libavutil/cast5.h",
+            "libavutil/channel_layout.h",
+            // Here until https://github.com/rust-lang/rust-bindgen/issues/2192 /
+            // https://github.com/rust-lang/rust-bindgen/issues/258 is fixed.
+            "channel_layout_fixed.h",
+            "libavutil/cpu.h",
+            "libavutil/crc.h",
+            "libavutil/dict.h",
+            "libavutil/display.h",
+            "libavutil/downmix_info.h",
+            "libavutil/error.h",
+            "libavutil/eval.h",
+            "libavutil/fifo.h",
+            "libavutil/file.h",
+            "libavutil/frame.h",
+            "libavutil/hash.h",
+            "libavutil/hmac.h",
+            "libavutil/hwcontext.h",
+            "libavutil/imgutils.h",
+            "libavutil/lfg.h",
+            "libavutil/log.h",
+            "libavutil/lzo.h",
+            "libavutil/macros.h",
+            "libavutil/mathematics.h",
+            "libavutil/md5.h",
+            "libavutil/mem.h",
+            "libavutil/motion_vector.h",
+            "libavutil/murmur3.h",
+            "libavutil/opt.h",
+            "libavutil/parseutils.h",
+            "libavutil/pixdesc.h",
+            "libavutil/pixfmt.h",
+            "libavutil/random_seed.h",
+            "libavutil/rational.h",
+            "libavutil/replaygain.h",
+            "libavutil/ripemd.h",
+            "libavutil/samplefmt.h",
+            "libavutil/sha.h",
+            "libavutil/sha512.h",
+            "libavutil/stereo3d.h",
+            "libavutil/avstring.h",
+            "libavutil/threadmessage.h",
+            "libavutil/time.h",
+            "libavutil/timecode.h",
+            "libavutil/twofish.h",
+            "libavutil/avutil.h",
+            "libavutil/xtea.h",
+        ],
+    },
+    Library {
+        name: "avcodec",
+        version: "6.0",
+        optional: true,
+        headers: &[
+            "libavcodec/avcodec.h",
+            "libavcodec/dv_profile.h",
+            "libavcodec/avfft.h",
+            "libavcodec/vorbis_parser.h",
+        ],
+    },
+    Library {
+        name: "avdevice",
+        version: "6.0",
+        optional: true,
+        headers: &["libavdevice/avdevice.h"],
+    },
+    Library {
+        name: "avfilter",
+        version: "6.0",
+        optional: true,
+        headers: &[
+            "libavfilter/buffersink.h",
+            "libavfilter/buffersrc.h",
+            "libavfilter/avfilter.h",
+        ],
+    },
+    Library {
+        name: "avformat",
+        version: "6.0",
+        optional: true,
+        headers: &["libavformat/avformat.h", "libavformat/avio.h"],
+    },
+    Library {
+        name: "postproc",
+        version: "6.0",
+        optional: true,
+        headers: &["libpostproc/postprocess.h"],
+    },
+    Library {
+        name: "swresample",
+        version: "4.7",
+        optional: true,
+        headers: &["libswresample/swresample.h"],
+    },
+    Library {
+        name: "swscale",
+        version: "6.0",
+        optional: true,
+        headers: &["libswscale/swscale.h"],
+    },
+];
+
+/// Hardware-acceleration `hwcontext_*.h` headers, each behind its own Cargo
+/// feature so users only pay for the accelerators they target (and, for
+/// `qsv`, the Intel Media SDK clone it needs).
+static HWACCELS: &[(&str, &str)] = &[
+    ("vaapi", "libavutil/hwcontext_vaapi.h"),
+    ("vulkan", "libavutil/hwcontext_vulkan.h"),
+    ("cuda", "libavutil/hwcontext_cuda.h"),
+    ("videotoolbox", "libavutil/hwcontext_videotoolbox.h"),
+    ("amf", "libavutil/hwcontext_amf.h"),
+    ("qsv", "libavutil/hwcontext_qsv.h"),
+    ("d3d11va", "libavutil/hwcontext_d3d11va.h"),
+    ("drm", "libavutil/hwcontext_drm.h"),
 ];
 
+/// Whether `CARGO_FEATURE_<NAME>` is set, i.e. the Cargo feature matching
+/// `name` was enabled for this build. Like Cargo, `<NAME>` is `name`
+/// upper-cased with `-` turned into `_`.
+fn cargo_feature_enabled(name: &str) -> bool {
+    env::var(format!(
+        "CARGO_FEATURE_{}",
+        name.to_uppercase().replace('-', "_")
+    ))
+    .is_ok()
+}
+
+/// Whether the `static` Cargo feature is enabled, i.e. every FFmpeg library
+/// (and its transitive deps, when discovered via pkg-config) should be
+/// linked statically instead of dynamically.
+fn static_linking_enabled() -> bool {
+    cargo_feature_enabled("static")
+}
+
+/// Names of the libraries that should be linked for this build: `avutil`
+/// plus every optional library whose Cargo feature is enabled.
+fn enabled_library_names() -> Vec<String> {
+    LIBRARYS
+        .iter()
+        .filter(|lib| !lib.optional || cargo_feature_enabled(lib.name))
+        .map(|lib| lib.name.to_string())
+        .collect()
+}
+
+/// Maps `LIBAVUTIL_VERSION_MAJOR` to the FFmpeg release it ships in, so
+/// this crate's code can write `#[cfg(ffmpeg_7_0)]` instead of chasing raw
+/// libavutil numbers. These are `cargo:rustc-cfg` flags, so they apply to
+/// this crate's own compilation only; crates that depend on it do not see
+/// them. A detected major version also implies every older entry (FFmpeg
+/// 7.x still satisfies `ffmpeg_6_0`).
+static FFMPEG_VERSIONS: &[(i64, &str)] =
+    &[(57, "ffmpeg_5_0"), (58, "ffmpeg_6_0"), (59, "ffmpeg_7_0")];
+
+/// Emits the `cargo:rustc-cfg` flags implied by the `LIBAVUTIL_VERSION_MAJOR`
+/// macro bindgen observed while parsing the headers.
+fn emit_version_cfg(versions: &HashMap<String, i64>) {
+    let Some(&major) = versions.get("LIBAVUTIL_VERSION_MAJOR") else {
+        return;
+    };
+
+    for &(min_major, cfg) in FFMPEG_VERSIONS {
+        if major >= min_major {
+            println!("cargo:rustc-cfg={}", cfg);
+        }
+    }
+}
+
 fn main() -> anyhow::Result<()> {
     let out_dir = env::var("OUT_DIR")?;
     let is_debug = env::var("DEBUG")
         .map(|label| label == "true")
         .unwrap_or(true);
 
-    let (mut include_prefix, lib_prefix) = find_ffmpeg_prefix(&out_dir, is_debug)?;
+    for &(_, cfg) in FFMPEG_VERSIONS {
+        println!("cargo:rustc-check-cfg=cfg({})", cfg);
+    }
+    for lib in &LIBRARYS {
+        println!("cargo:rustc-check-cfg=cfg({})", lib.name);
+    }
+
+    let (mut include_prefix, lib_prefix, link_libs) = find_ffmpeg_prefix(&out_dir, is_debug)?;
     for path in &lib_prefix {
         println!("cargo:rustc-link-search=all={}", path);
     }
 
-    for (lib, _) in LIBRARYS {
-        println!("cargo:rustc-link-lib={}", lib);
+    let is_static = static_linking_enabled();
+    for name in &link_libs {
+        if is_static {
+            println!("cargo:rustc-link-lib=static={}", name);
+        } else {
+            println!("cargo:rustc-link-lib={}", name);
+        }
+    }
+
+    for lib in &LIBRARYS {
+        if lib.optional && !cargo_feature_enabled(lib.name) {
+            continue;
+        }
+
+        println!("cargo:rustc-cfg={}", lib.name);
     }
 
     if cfg!(target_os = "macos") {
@@ -168,21 +418,25 @@ fn main() -> anyhow::Result<()> {
         }
     }
 
-    let media_sdk_prefix = join(&out_dir, "media-sdk").unwrap();
-    if !is_exsit(&media_sdk_prefix) {
-        exec(
-            "git clone https://github.com/Intel-Media-SDK/MediaSDK media-sdk",
-            &out_dir,
-        )?;
-    }
+    if cargo_feature_enabled("qsv") {
+        let media_sdk_prefix = join(&out_dir, "media-sdk").unwrap();
+        if !is_exsit(&media_sdk_prefix) {
+            exec(
+                "git clone https://github.com/Intel-Media-SDK/MediaSDK media-sdk",
+                &out_dir,
+            )?;
+        }
 
-    let media_sdk_include_prefix = join(&media_sdk_prefix, "./api/include")?;
-    include_prefix.append(&mut vec![media_sdk_include_prefix.clone()]);
+        let media_sdk_include_prefix = join(&media_sdk_prefix, "./api/include")?;
+        include_prefix.append(&mut vec![media_sdk_include_prefix.clone()]);
+    }
 
     let clang_includes = include_prefix
         .iter()
         .map(|include| format!("-I{}", include));
 
+    let versions: Rc<RefCell<HashMap<String, i64>>> = Rc::new(RefCell::new(HashMap::new()));
+
     // The bindgen::Builder is the main entry point
     // to bindgen, and lets you build up options for
     // the resulting bindings.
@@ -191,6 +445,12 @@ fn main() -> anyhow::Result<()> {
         .ctypes_prefix("libc")
         // https://github.com/rust-lang/rust-bindgen/issues/550
         .blocklist_type("max_align_t")
+        // Matches leading-underscore libc internals only (e.g. `_IO_*`);
+        // nothing under `avio_*` starts with `_`, so this rule does not
+        // need an `allowlist`/`opaque` carve-out for the AVIO callback API
+        // (AVIOContext, avio_alloc_context, avio_open_dyn_buf,
+        // avio_close_dyn_buf) — checked explicitly by
+        // assert_avio_symbols_present below rather than assumed.
         .blocklist_function("_.*")
         // Blocklist functions with u128 in signature.
         // https://github.com/zmwangx/rust-ffmpeg-sys/issues/1
@@ -283,98 +543,30 @@ fn main() -> anyhow::Result<()> {
         .prepend_enum_name(false)
         .derive_eq(true)
         .size_t_is_usize(true)
-        .parse_callbacks(Box::new(Callbacks))
-        .header(search_include(&include_prefix, "libavcodec/avcodec.h"))
-        .header(search_include(&include_prefix, "libavcodec/dv_profile.h"))
-        .header(search_include(&include_prefix, "libavcodec/avfft.h"))
-        .header(search_include(
-            &include_prefix,
-            "libavcodec/vorbis_parser.h",
-        ))
-        .header(search_include(&include_prefix, "libavdevice/avdevice.h"))
-        .header(search_include(&include_prefix, "libavfilter/buffersink.h"))
-        .header(search_include(&include_prefix, "libavfilter/buffersrc.h"))
-        .header(search_include(&include_prefix, "libavfilter/avfilter.h"))
-        .header(search_include(&include_prefix, "libavformat/avformat.h"))
-        .header(search_include(&include_prefix, "libavformat/avio.h"))
-        .header(search_include(&include_prefix, "libavutil/adler32.h"))
-        .header(search_include(&include_prefix, "libavutil/aes.h"))
-        .header(search_include(&include_prefix, "libavutil/audio_fifo.h"))
-        .header(search_include(&include_prefix, "libavutil/base64.h"))
-        .header(search_include(&include_prefix, "libavutil/blowfish.h"))
-        .header(search_include(&include_prefix, "libavutil/bprint.h"))
-        .header(search_include(&include_prefix, "libavutil/buffer.h"))
-        .header(search_include(&include_prefix, "libavutil/camellia.h"))
-        .header(search_include(&include_prefix, "libavutil/cast5.h"))
-        .header(search_include(
-            &include_prefix,
-            "libavutil/channel_layout.h",
-        ))
-        // Here until https://github.com/rust-lang/rust-bindgen/issues/2192 /
-        // https://github.com/rust-lang/rust-bindgen/issues/258 is fixed.
-        .header("channel_layout_fixed.h")
-        .header(search_include(&include_prefix, "libavutil/cpu.h"))
-        .header(search_include(&include_prefix, "libavutil/crc.h"))
-        .header(search_include(&include_prefix, "libavutil/dict.h"))
-        .header(search_include(&include_prefix, "libavutil/display.h"))
-        .header(search_include(&include_prefix, "libavutil/downmix_info.h"))
-        .header(search_include(&include_prefix, "libavutil/error.h"))
-        .header(search_include(&include_prefix, "libavutil/eval.h"))
-        .header(search_include(&include_prefix, "libavutil/fifo.h"))
-        .header(search_include(&include_prefix, "libavutil/file.h"))
-        .header(search_include(&include_prefix, "libavutil/frame.h"))
-        .header(search_include(&include_prefix, "libavutil/hash.h"))
-        .header(search_include(&include_prefix, "libavutil/hmac.h"))
-        .header(search_include(&include_prefix, "libavutil/hwcontext.h"))
-        .header(search_include(&include_prefix, "libavutil/imgutils.h"))
-        .header(search_include(&include_prefix, "libavutil/lfg.h"))
-        .header(search_include(&include_prefix, "libavutil/log.h"))
-        .header(search_include(&include_prefix, "libavutil/lzo.h"))
-        .header(search_include(&include_prefix, "libavutil/macros.h"))
-        .header(search_include(&include_prefix, "libavutil/mathematics.h"))
-        .header(search_include(&include_prefix, "libavutil/md5.h"))
-        .header(search_include(&include_prefix, "libavutil/mem.h"))
-        .header(search_include(&include_prefix, "libavutil/motion_vector.h"))
-        .header(search_include(&include_prefix, "libavutil/murmur3.h"))
-        .header(search_include(&include_prefix, "libavutil/opt.h"))
-        .header(search_include(&include_prefix, "libavutil/parseutils.h"))
-        .header(search_include(&include_prefix, "libavutil/pixdesc.h"))
-        .header(search_include(&include_prefix, "libavutil/pixfmt.h"))
-        .header(search_include(&include_prefix, "libavutil/random_seed.h"))
-        .header(search_include(&include_prefix, "libavutil/rational.h"))
-        .header(search_include(&include_prefix, "libavutil/replaygain.h"))
-        .header(search_include(&include_prefix, "libavutil/ripemd.h"))
-        .header(search_include(&include_prefix, "libavutil/samplefmt.h"))
-        .header(search_include(&include_prefix, "libavutil/sha.h"))
-        .header(search_include(&include_prefix, "libavutil/sha512.h"))
-        .header(search_include(&include_prefix, "libavutil/stereo3d.h"))
-        .header(search_include(&include_prefix, "libavutil/avstring.h"))
-        .header(search_include(&include_prefix, "libavutil/threadmessage.h"))
-        .header(search_include(&include_prefix, "libavutil/time.h"))
-        .header(search_include(&include_prefix, "libavutil/timecode.h"))
-        .header(search_include(&include_prefix, "libavutil/twofish.h"))
-        .header(search_include(&include_prefix, "libavutil/avutil.h"))
-        .header(search_include(&include_prefix, "libavutil/xtea.h"))
-        .header(search_include(&include_prefix, "libpostproc/postprocess.h"))
-        .header(search_include(
-            &include_prefix,
-            "libswresample/swresample.h",
-        ))
-        .header(search_include(&include_prefix, "libpostproc/postprocess.h"));
-
-    #[cfg(target_os = "windows")]
-    {
-        builder = builder
-            .header(search_include(&include_prefix, "libavutil/hwcontext_qsv.h"))
-            .header(search_include(
-                &include_prefix,
-                "libavutil/hwcontext_d3d11va.h",
-            ));
+        .parse_callbacks(Box::new(Callbacks {
+            versions: versions.clone(),
+        }));
+
+    for lib in &LIBRARYS {
+        if lib.optional && !cargo_feature_enabled(lib.name) {
+            continue;
+        }
+
+        for header in lib.headers {
+            // channel_layout_fixed.h lives next to build.rs, not under an
+            // FFmpeg include prefix.
+            builder = builder.header(if *header == "channel_layout_fixed.h" {
+                header.to_string()
+            } else {
+                search_include(&include_prefix, header)
+            });
+        }
     }
 
-    #[cfg(target_os = "linux")]
-    {
-        builder = builder.header(search_include(&include_prefix, "libavutil/hwcontext_drm.h"));
+    for &(feature, header) in HWACCELS {
+        if cargo_feature_enabled(feature) {
+            builder = builder.header(search_include(&include_prefix, header));
+        }
     }
 
     // Finish the builder and generate the bindings.
@@ -383,28 +575,115 @@ fn main() -> anyhow::Result<()> {
         // Unwrap the Result and panic on failure.
         .expect("Unable to generate bindings");
 
+    emit_version_cfg(&versions.borrow());
+
     // Write the bindings to the $OUT_DIR/bindings.rs file.
+    let bindings_path = output().join("bindings.rs");
     bindings
-        .write_to_file(output().join("bindings.rs"))
+        .write_to_file(&bindings_path)
         .expect("Couldn't write bindings!");
 
+    if cargo_feature_enabled("avformat") {
+        assert_avio_symbols_present(&bindings_path);
+    }
+
     Ok(())
 }
 
-fn find_ffmpeg_prefix(out_dir: &str, is_debug: bool) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+/// Whether the source-build fallback was requested, either via the
+/// `build-from-source` Cargo feature or the `FFMPEG_BUILD_SOURCE` env var
+/// (handy for quick local overrides without touching `Cargo.toml`).
+fn build_from_source_requested() -> bool {
+    cargo_feature_enabled("build-from-source") || env::var("FFMPEG_BUILD_SOURCE").is_ok()
+}
+
+fn cpu_count() -> usize {
+    std::thread::available_parallelism()
+        .map(|cores| cores.get())
+        .unwrap_or(1)
+}
+
+/// Clones a specific FFmpeg release tag and builds it via its own
+/// `configure`/`make`, staging the result under `$OUT_DIR`. This trades the
+/// convenience of a system/prebuilt FFmpeg for reproducible, codec-tailored
+/// builds: the `FFMPEG_BUILD_VERSION` and `FFMPEG_CONFIGURE_FLAGS` env vars
+/// pick the release tag and the enabled/disabled codecs respectively.
+fn build_from_source(out_dir: &str) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+    let version = env::var("FFMPEG_BUILD_VERSION").unwrap_or_else(|_| "n6.1".to_string());
+    let source_prefix = join(out_dir, "ffmpeg-source")?;
+    let install_prefix = join(out_dir, "ffmpeg-install")?;
+
+    if !is_exsit(&source_prefix) {
+        exec(
+            &format!(
+                "git clone --depth 1 --branch {} https://github.com/FFmpeg/FFmpeg ffmpeg-source",
+                version
+            ),
+            out_dir,
+        )?;
+    }
+
+    if !is_exsit(&install_prefix) {
+        let configure_flags = env::var("FFMPEG_CONFIGURE_FLAGS").unwrap_or_else(|_| {
+            if static_linking_enabled() {
+                "--disable-gpl --enable-static --disable-shared".to_string()
+            } else {
+                "--disable-gpl --enable-shared --disable-static".to_string()
+            }
+        });
+
+        exec(
+            &format!(
+                "./configure --prefix={} {}",
+                install_prefix, configure_flags
+            ),
+            &source_prefix,
+        )?;
+        exec(&format!("make -j{}", cpu_count()), &source_prefix)?;
+        exec("make install", &source_prefix)?;
+    }
+
+    Ok((
+        vec![join(&install_prefix, "./include")?],
+        vec![join(&install_prefix, "./lib")?],
+    ))
+}
+
+/// Resolves FFmpeg's include/lib directories and the libraries to link
+/// against. Returns `(includes, lib_search_paths, link_libs)`; `link_libs`
+/// carries transitive deps (e.g. `x264`, `opus`) when pkg-config reports
+/// them for a [`static_linking_enabled`] build.
+fn find_ffmpeg_prefix(
+    out_dir: &str,
+    is_debug: bool,
+) -> anyhow::Result<(Vec<String>, Vec<String>, Vec<String>)> {
+    if build_from_source_requested() {
+        let (includes, libs) = build_from_source(out_dir)?;
+        return Ok((includes, libs, enabled_library_names()));
+    }
+
+    if let Ok(dir) = env::var("FFMPEG_DIR") {
+        return Ok((
+            vec![join(&dir, "include")?],
+            vec![join(&dir, "lib")?],
+            enabled_library_names(),
+        ));
+    }
+
     if cfg!(target_os = "macos") {
         let prefix = exec("brew --prefix ffmpeg@6", out_dir)?.replace('\n', "");
 
         Ok((
             vec![join(&prefix, "./include")?],
             vec![join(&prefix, "./lib")?],
+            enabled_library_names(),
         ))
     } else if cfg!(target_os = "windows") {
         let prefix = join(out_dir, "ffmpeg").unwrap();
         if !is_exsit(&prefix) {
             exec(
                     &format!(
-                        "Invoke-WebRequest -Uri https://github.com/mycrl/third-party/releases/download/distributions/ffmpeg-windows-x64-{}.zip -OutFile ffmpeg.zip", 
+                        "Invoke-WebRequest -Uri https://github.com/mycrl/third-party/releases/download/distributions/ffmpeg-windows-x64-{}.zip -OutFile ffmpeg.zip",
                         if is_debug { "debug" } else { "release" }
                     ),
                     out_dir,
@@ -419,15 +698,24 @@ fn find_ffmpeg_prefix(out_dir: &str, is_debug: bool) -> anyhow::Result<(Vec<Stri
         Ok((
             vec![join(&prefix, "./include")?],
             vec![join(&prefix, "./lib")?],
+            enabled_library_names(),
         ))
     } else {
+        let is_static = static_linking_enabled();
         let mut librarys = Vec::new();
         let mut includes = Vec::new();
+        let mut link_libs = Vec::new();
+
+        for lib in &LIBRARYS {
+            if lib.optional && !cargo_feature_enabled(lib.name) {
+                continue;
+            }
 
-        for (name, version) in LIBRARYS {
             let lib = pkg_config::Config::new()
-                .atleast_version(version)
-                .probe(&format!("lib{}", name))?;
+                .atleast_version(lib.version)
+                .statik(is_static)
+                .cargo_metadata(false)
+                .probe(&format!("lib{}", lib.name))?;
 
             for path in lib.link_paths {
                 librarys.push(path.to_str().unwrap().to_string());
@@ -436,8 +724,14 @@ fn find_ffmpeg_prefix(out_dir: &str, is_debug: bool) -> anyhow::Result<(Vec<Stri
             for path in lib.include_paths {
                 includes.push(path.to_str().unwrap().to_string());
             }
+
+            for name in lib.libs {
+                if !link_libs.contains(&name) {
+                    link_libs.push(name);
+                }
+            }
         }
 
-        Ok((includes, librarys))
+        Ok((includes, librarys, link_libs))
     }
 }